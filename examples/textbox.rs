@@ -18,7 +18,7 @@ fn main() {
 
     let text_style = vec!{
         TextStyleField::Text(Value::Single("I believe in reincarnation.\nThat's why I eat Jello.\nIt's good for the stomach".to_owned())),
-        TextStyleField::BackgroundColor(Value::Single(WHITE)),
+        TextStyleField::BackgroundColor(Value::Theme("background_color".to_owned())),
     };
     let text_drawable = text::text_drawable(text_style);
 
@@ -1,11 +1,14 @@
+use std::collections::HashMap;
 
 use backend::gfx::G2d;
 
-use petgraph::Graph;
+use petgraph::stable_graph::StableGraph;
 use petgraph::graph::NodeIndex;
 use petgraph::visit::Dfs;
+use petgraph::Direction;
 
-use input::{Event, GenericEvent, MouseCursorEvent, UpdateArgs};
+use input::{Event, EventId, GenericEvent, MouseCursorEvent, PressEvent, ReleaseEvent, UpdateArgs};
+use input::{Button, Key, Input, Motion};
 
 use cassowary::{Solver, Constraint};
 use cassowary::WeightedRelation::*;
@@ -30,6 +33,7 @@ pub struct Resources {
     pub glyph_cache: GlyphCache,
     pub fonts: resources::Map<Font>,
     pub images: resources::Map<Texture>,
+    pub theme: Theme,
 }
 impl Resources {
     fn new(glyph_cache: GlyphCache) -> Self {
@@ -39,38 +43,108 @@ impl Resources {
             fonts: fonts,
             images: images,
             glyph_cache: glyph_cache,
+            theme: Theme::new(),
         }
     }
 }
 
+// A single default style value a widget can fall back to when it leaves a
+// style field unset. `Value::Theme(key)` resolves against whichever of these
+// matches its key in the active `Theme`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ThemeValue {
+    Color([f32; 4]),
+    Font(String),
+    Number(f64),
+}
+
+// Conrod-style default styles, keyed so widgets (and the `Value::Theme(key)`
+// style resolution) can look up defaults by name instead of every builder
+// inlining its own colors/fonts/sizes. `Ui::set_theme` swaps the active theme
+// in one call; only style values a widget's draw path passes through
+// `Value::resolve` pick up the new defaults.
+pub struct Theme {
+    values: HashMap<String, ThemeValue>,
+}
+impl Theme {
+    pub fn new() -> Self {
+        let mut values = HashMap::new();
+        values.insert("background_color".to_owned(), ThemeValue::Color([1.0, 1.0, 1.0, 1.0]));
+        values.insert("foreground_color".to_owned(), ThemeValue::Color([0.0, 0.0, 0.0, 1.0]));
+        values.insert("padding".to_owned(), ThemeValue::Number(4.0));
+        values.insert("corner_radius".to_owned(), ThemeValue::Number(0.0));
+        Theme { values: values }
+    }
+    pub fn get(&self, key: &str) -> Option<&ThemeValue> {
+        self.values.get(key)
+    }
+    pub fn set(&mut self, key: &str, value: ThemeValue) {
+        self.values.insert(key.to_owned(), value);
+    }
+}
+
 pub struct InputState {
     pub mouse: Point,
+    pub focused: Option<NodeIndex>,
+    shift_held: bool,
+    // Last frame's topmost-under-cursor widget, so crossing a widget's
+    // boundary can be detected and turned into enter/leave events.
+    hovered: Option<NodeIndex>,
+    // The widget that received the initial press, captured so drag events
+    // keep reaching it even after the cursor leaves its bounds.
+    drag: Option<DragState>,
 }
 impl InputState {
     fn new() -> Self {
-        InputState { mouse: Point { x: 0.0, y: 0.0 }}
+        InputState {
+            mouse: Point { x: 0.0, y: 0.0 },
+            focused: None,
+            shift_held: false,
+            hovered: None,
+            drag: None,
+        }
     }
 }
 
-pub struct Ui {
-    pub graph: Graph<Widget, ()>,
+struct DragState {
+    widget: NodeIndex,
+    start: Point,
+    last: Point,
+}
+
+pub struct Ui<S> {
+    // `StableGraph` rather than plain `Graph`: `remove_widget_live` removes
+    // nodes at runtime, and a plain `Graph::remove_node` swap-removes,
+    // silently reassigning the last node's index and invalidating every
+    // `NodeIndex` this `Ui` (and its callers) have stored elsewhere
+    // (`constraints` keys, `input_state.focused`/`hovered`/`drag`).
+    // `StableGraph::remove_node` leaves a tombstone instead, so indices
+    // stay valid for the lifetime of the node they were issued for.
+    pub graph: StableGraph<Widget<S>, ()>,
     pub root_index: NodeIndex,
-    constraints: Vec<Constraint>,
+    // Constraints currently registered with `solver`, tracked per widget so
+    // `remove_widget_live` can find exactly what to tear down.
+    constraints: HashMap<NodeIndex, Vec<Constraint>>,
+    // Last solved bounds per widget, reused by `draw` for widgets whose
+    // layout hasn't changed since instead of re-querying the solver for
+    // every widget on every frame.
+    bounds_cache: HashMap<NodeIndex, Rectangle>,
     pub solver: Solver,
     pub resources: Resources,
     pub input_state: InputState,
 }
-impl Ui {
+impl<S> Ui<S> {
     pub fn new(window: &mut Window, window_dims: Dimensions) -> Self {
         let root = Widget::new(widget::primitives::draw_nothing, Box::new(EmptyDrawable {}));
-        let mut constraints = Vec::new();
         let mut solver = Solver::new();
 
-        let mut graph = Graph::<Widget, ()>::new();
+        let mut graph = StableGraph::<Widget<S>, ()>::new();
         solver.add_edit_variable(root.layout.right, STRONG).unwrap();
         solver.add_edit_variable(root.layout.bottom, STRONG).unwrap();
-        constraints.push(root.layout.left | EQ(STRONG) | 0.0);
-        constraints.push(root.layout.top | EQ(STRONG) | 0.0);
+        let root_constraints = vec![
+            root.layout.left | EQ(STRONG) | 0.0,
+            root.layout.top | EQ(STRONG) | 0.0,
+        ];
         let root_index = graph.add_node(root);
 
         let glyph_cache = GlyphCache::new(&mut window.context.factory,
@@ -79,11 +153,14 @@ impl Ui {
 
         let resources = Resources::new(glyph_cache);
         let input_state = InputState::new();
+        let mut constraints = HashMap::new();
+        constraints.insert(root_index, root_constraints);
         let mut ui = Ui {
             graph: graph,
             root_index: root_index,
             solver: solver,
             constraints: constraints,
+            bounds_cache: HashMap::new(),
             resources: resources,
             input_state: input_state,
         };
@@ -91,75 +168,419 @@ impl Ui {
         ui
     }
     pub fn resize_window(&mut self, window_dims: Dimensions) {
-        let ref root = self.graph[self.root_index];
-        self.solver.suggest_value(root.layout.right, window_dims.width).unwrap();
-        self.solver.suggest_value(root.layout.bottom, window_dims.height).unwrap();
+        {
+            let ref root = self.graph[self.root_index];
+            self.solver.suggest_value(root.layout.right, window_dims.width).unwrap();
+            self.solver.suggest_value(root.layout.bottom, window_dims.height).unwrap();
+        }
+        self.graph[self.root_index].mark_dirty();
     }
     pub fn init(&mut self) {
         let mut dfs = Dfs::new(&self.graph, self.root_index);
         while let Some(node_index) = dfs.next(&self.graph) {
-            let ref mut node = self.graph[node_index];
-            let constraints = &mut node.layout.constraints;
-            self.constraints.append(constraints);
+            let widget_constraints: Vec<Constraint> = {
+                let ref mut node = self.graph[node_index];
+                node.layout.constraints.drain(..).collect()
+            };
+            self.constraints.entry(node_index).or_insert_with(Vec::new).extend(widget_constraints);
         }
-        self.solver.add_constraints(&self.constraints).unwrap();
+        let all_constraints: Vec<Constraint> = self.constraints.values().flat_map(|c| c.iter().cloned()).collect();
+        self.solver.add_constraints(&all_constraints).unwrap();
     }
-    pub fn draw(&mut self, c: Context, g: &mut G2d) {
+    // Draws every widget every frame: `c`/`g` here come from a piston2d
+    // frame that's cleared before this runs, so there's no retained
+    // framebuffer a dirty-widget could skip redrawing into. layout_dirty
+    // still earns its keep here though: a clean widget's bounds can't have
+    // changed since last frame, so its cached bounds are reused instead of
+    // querying the solver again, which is the only part of this loop that
+    // scales with how much of the tree actually changed.
+    pub fn draw(&mut self, state: &mut S, c: Context, g: &mut G2d) {
+        self.propagate_layout_dirty();
         let mut dfs = Dfs::new(&self.graph, self.root_index);
         while let Some(node_index) = dfs.next(&self.graph) {
+            let layout_dirty = self.graph[node_index].layout_dirty;
+            if layout_dirty || !self.bounds_cache.contains_key(&node_index) {
+                let bounds = self.graph[node_index].layout.bounds(&mut self.solver);
+                self.bounds_cache.insert(node_index, bounds);
+            }
+            let bounds = self.bounds_cache.get(&node_index).unwrap().clone();
             let ref widget = self.graph[node_index];
             if DEBUG_BOUNDS {
-                draw_rect_outline(widget.layout.bounds(&mut self.solver),
-                                  [0.0, 1.0, 1.0, 1.0],
-                                  c,
-                                  g);
+                draw_rect_outline(bounds, [0.0, 1.0, 1.0, 1.0], c, g);
             }
-            widget.draw(&mut self.resources, &mut self.solver, c, g);
+            widget.draw(bounds, &mut self.resources, c, g, state);
         }
+        self.clear_dirty_flags();
+    }
+    // A layout-dirty widget's descendants may depend on it through the
+    // cassowary solver, so dirtiness cascades down before the frame draws.
+    fn propagate_layout_dirty(&mut self) {
+        let mut stack = vec![(self.root_index, false)];
+        while let Some((node_index, inherited_dirty)) = stack.pop() {
+            let dirty = {
+                let ref mut widget = self.graph[node_index];
+                if inherited_dirty {
+                    widget.mark_dirty();
+                }
+                widget.layout_dirty
+            };
+            let children: Vec<NodeIndex> = self.graph.neighbors(node_index).collect();
+            stack.extend(children.into_iter().map(|child_index| (child_index, dirty)));
+        }
+    }
+    fn clear_dirty_flags(&mut self) {
+        let mut dfs = Dfs::new(&self.graph, self.root_index);
+        while let Some(node_index) = dfs.next(&self.graph) {
+            let ref mut widget = self.graph[node_index];
+            widget.layout_dirty = false;
+            widget.paint_dirty = false;
+        }
+    }
+    // Swaps the active theme and marks every widget paint-dirty, so widgets
+    // that resolve colors/fonts/sizes via `Value::Theme(key)` redraw with the
+    // new defaults on the next frame instead of needing to be rebuilt.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.resources.theme = theme;
+        let mut dfs = Dfs::new(&self.graph, self.root_index);
+        while let Some(node_index) = dfs.next(&self.graph) {
+            self.graph[node_index].mark_dirty();
+        }
+    }
+    pub fn add_widget(&mut self, parent_index: NodeIndex, child: Widget<S>) -> NodeIndex {
+        let child_index = self.graph.add_node(child);
+        self.graph.add_edge(parent_index, child_index, ());
+
+        let (parent, child) = self.graph.index_twice_mut(parent_index, child_index);
+        child.layout.bound_by(&parent.layout);
+
+        child_index
     }
-    pub fn add_widget(&mut self, parent_index: NodeIndex, child: Widget) -> NodeIndex {
+    // Like `add_widget`, but for widgets added after `init()` has already run:
+    // its constraints are registered with the live solver one at a time
+    // instead of waiting to be batched into a bulk `add_constraints` call,
+    // so a new list row or dialog can be inserted without re-solving from
+    // scratch.
+    pub fn add_widget_live(&mut self, parent_index: NodeIndex, child: Widget<S>) -> NodeIndex {
         let child_index = self.graph.add_node(child);
         self.graph.add_edge(parent_index, child_index, ());
+        // New constraints can move widgets anywhere in the tree, so the whole
+        // tree's cached bounds are refreshed on the next draw.
+        self.graph[self.root_index].mark_dirty();
 
         let (parent, child) = self.graph.index_twice_mut(parent_index, child_index);
         child.layout.bound_by(&parent.layout);
+        let widget_constraints: Vec<Constraint> = child.layout.constraints.drain(..).collect();
 
+        for constraint in &widget_constraints {
+            self.solver.add_constraint(constraint.clone()).unwrap();
+        }
+        self.constraints.insert(child_index, widget_constraints);
         child_index
     }
-    pub fn handle_event(&mut self, event: &Event) {
+    // Removes exactly this widget's constraints and edit variables from the
+    // live solver before dropping the node, so repeatedly adding/removing
+    // rows at runtime doesn't leak solver state that eventually conflicts.
+    pub fn remove_widget_live(&mut self, node_index: NodeIndex) {
+        // A child becomes unreachable from root the instant its parent's
+        // edges are severed, so the whole subtree has to be collected up
+        // front; tearing down only `node_index` would leave every
+        // descendant's constraints and edit variables orphaned in the live
+        // solver, permanently leaking them, for exactly the composite
+        // widgets (list rows, dialogs) this method is meant to support.
+        let mut subtree = Vec::new();
+        let mut stack = vec![node_index];
+        while let Some(index) = stack.pop() {
+            subtree.push(index);
+            stack.extend(self.graph.neighbors(index));
+        }
+        for index in subtree {
+            if let Some(constraints) = self.constraints.remove(&index) {
+                for constraint in &constraints {
+                    self.solver.remove_constraint(constraint).unwrap();
+                }
+            }
+            {
+                let ref widget = self.graph[index];
+                let edit_vars = [widget.layout.left, widget.layout.top, widget.layout.right, widget.layout.bottom];
+                for edit_var in &edit_vars {
+                    if self.solver.has_edit_variable(edit_var) {
+                        self.solver.remove_edit_variable(edit_var).unwrap();
+                    }
+                }
+            }
+            self.graph.remove_node(index);
+            self.bounds_cache.remove(&index);
+
+            if self.input_state.focused == Some(index) {
+                self.input_state.focused = None;
+            }
+            if self.input_state.hovered == Some(index) {
+                self.input_state.hovered = None;
+            }
+            if self.input_state.drag.as_ref().map_or(false, |drag| drag.widget == index) {
+                self.input_state.drag = None;
+            }
+        }
+        // The removed constraints can move the remaining widgets, so their
+        // cached bounds are refreshed on the next draw.
+        if let Some(root) = self.graph.node_weight_mut(self.root_index) {
+            root.mark_dirty();
+        }
+    }
+    pub fn handle_event(&mut self, state: &mut S, event: &Event) {
         if let Some(mouse) = event.mouse_cursor_args() {
             self.input_state.mouse = mouse.into();
         }
-        self.post_event(event);
+        if let Some(Button::Keyboard(key)) = event.press_args() {
+            match key {
+                Key::LShift | Key::RShift => self.input_state.shift_held = true,
+                Key::Tab if self.input_state.shift_held => {
+                    self.focus_previous(state);
+                    // Tab is consumed by focus traversal; don't let it also
+                    // fall through to post_event and get delivered as
+                    // WIDGET_KEY to the widget focus was just moved to.
+                    return;
+                }
+                Key::Tab => {
+                    self.focus_next(state);
+                    return;
+                }
+                _ => {}
+            }
+        }
+        if let Some(Button::Keyboard(key)) = event.release_args() {
+            match key {
+                Key::LShift | Key::RShift => self.input_state.shift_held = false,
+                _ => {}
+            }
+        }
+        self.post_event(state, event);
     }
-    pub fn post_event(&mut self, event: &Event) {
+    pub fn post_event(&mut self, state: &mut S, event: &Event) {
         let mut new_events = Vec::new();
-        let id_registered = |widget: &Widget, id| { widget.event_handlers.iter().any(|event_handler| event_handler.event_id() == id) };
-        
-        let mut dfs = Dfs::new(&self.graph, self.root_index);
-        while let Some(node_index) = dfs.next(&self.graph) {
-            let ref mut widget = self.graph[node_index];
+        let id_registered = |widget: &Widget<S>, id| { widget.event_handlers.iter().any(|event_handler| event_handler.event_id() == id) };
 
-            let is_mouse_over = widget.is_mouse_over(&mut self.solver, self.input_state.mouse);
-            if is_mouse_over {
-                if event.event_id() == event::MOUSE_CURSOR && id_registered(widget, event::WIDGET_MOUSE_OVER) {
-                    widget.trigger_event(event::WIDGET_MOUSE_OVER, event);
-                }
-                if event.event_id() == event::PRESS && id_registered(widget, event::WIDGET_PRESS) {
-                    if let Some(event_id) = widget.trigger_event(event::WIDGET_PRESS, event) {
+        if let Some(Button::Keyboard(_)) = event.press_args() {
+            // Keyboard input is routed to the focused widget rather than
+            // whatever happens to be under the cursor, bubbling up to the
+            // nearest ancestor with a WIDGET_KEY handler if the focused
+            // widget itself doesn't have one.
+            if let Some(focused_index) = self.input_state.focused {
+                if let Some(node_index) = self.bubble_to_handler(focused_index, event::WIDGET_KEY) {
+                    let ref mut widget = self.graph[node_index];
+                    if let Some(event_id) = widget.trigger_event(event::WIDGET_KEY, event, state) {
                         new_events.push((node_index, event_id));
                     }
                 }
             }
+        } else {
+            let topmost = self.topmost_under_cursor();
+            self.update_hover(topmost, state);
+
+            if let Some(node_index) = topmost {
+                let is_press = event.event_id() == event::PRESS;
+                let focusable = self.graph[node_index].focusable;
+                {
+                    let ref mut widget = self.graph[node_index];
+                    if event.event_id() == event::MOUSE_CURSOR && id_registered(widget, event::WIDGET_MOUSE_OVER) {
+                        widget.trigger_event(event::WIDGET_MOUSE_OVER, event, state);
+                    }
+                    if is_press && id_registered(widget, event::WIDGET_PRESS) {
+                        if let Some(event_id) = widget.trigger_event(event::WIDGET_PRESS, event, state) {
+                            new_events.push((node_index, event_id));
+                        }
+                    }
+                }
+                if is_press && focusable {
+                    self.set_focus(state, Some(node_index));
+                }
+                if is_press {
+                    self.start_drag(node_index, state);
+                }
+            }
+            if event.event_id() == event::MOUSE_CURSOR {
+                self.update_drag(state);
+            }
+            if let Some(Button::Mouse(_)) = event.release_args() {
+                self.end_drag(state);
+            }
         }
         for (node_index, event_id) in new_events {
             let mut dfs = Dfs::new(&self.graph, self.root_index);
             while let Some(node_index) = dfs.next(&self.graph) {
                 let ref mut widget = self.graph[node_index];
                 if id_registered(widget, event_id) {
-                    widget.trigger_event(event_id, &Event::Update(UpdateArgs{dt:0.0}));
+                    widget.trigger_event(event_id, &Event::Update(UpdateArgs{dt:0.0}), state);
                 }
             }
         }
     }
+    // Moves focus to `node_index` (or clears it), firing WIDGET_BLUR on the
+    // previously focused widget and WIDGET_FOCUS on the new one so they can
+    // repaint their focus ring.
+    pub fn set_focus(&mut self, state: &mut S, node_index: Option<NodeIndex>) {
+        if self.input_state.focused == node_index {
+            return;
+        }
+        if let Some(old_index) = self.input_state.focused {
+            let ref mut widget = self.graph[old_index];
+            if widget.event_handlers.iter().any(|handler| handler.event_id() == event::WIDGET_BLUR) {
+                widget.trigger_event(event::WIDGET_BLUR, &Event::Update(UpdateArgs{dt:0.0}), state);
+            }
+        }
+        self.input_state.focused = node_index;
+        if let Some(new_index) = node_index {
+            let ref mut widget = self.graph[new_index];
+            if widget.event_handlers.iter().any(|handler| handler.event_id() == event::WIDGET_FOCUS) {
+                widget.trigger_event(event::WIDGET_FOCUS, &Event::Update(UpdateArgs{dt:0.0}), state);
+            }
+        }
+    }
+    // Diffs this frame's topmost-under-cursor widget against last frame's,
+    // firing WIDGET_MOUSE_LEAVE/WIDGET_MOUSE_ENTER on the boundary crossing
+    // instead of re-firing WIDGET_MOUSE_OVER every frame the cursor merely
+    // stays inside the same widget.
+    fn update_hover(&mut self, topmost: Option<NodeIndex>, state: &mut S) {
+        if topmost == self.input_state.hovered {
+            return;
+        }
+        if let Some(old_index) = self.input_state.hovered {
+            let ref mut widget = self.graph[old_index];
+            if widget.event_handlers.iter().any(|handler| handler.event_id() == event::WIDGET_MOUSE_LEAVE) {
+                widget.trigger_event(event::WIDGET_MOUSE_LEAVE, &Event::Update(UpdateArgs{dt:0.0}), state);
+            }
+        }
+        self.input_state.hovered = topmost;
+        if let Some(new_index) = topmost {
+            let ref mut widget = self.graph[new_index];
+            if widget.event_handlers.iter().any(|handler| handler.event_id() == event::WIDGET_MOUSE_ENTER) {
+                widget.trigger_event(event::WIDGET_MOUSE_ENTER, &Event::Update(UpdateArgs{dt:0.0}), state);
+            }
+        }
+    }
+    // Captures `node_index` as the widget dragging started on, so it keeps
+    // receiving WIDGET_DRAG/WIDGET_DRAG_END even once the cursor leaves its
+    // bounds (standard capture semantics for sliders, resizable panels).
+    fn start_drag(&mut self, node_index: NodeIndex, state: &mut S) {
+        let mouse = self.input_state.mouse;
+        self.input_state.drag = Some(DragState { widget: node_index, start: mouse, last: mouse });
+        let ref mut widget = self.graph[node_index];
+        if widget.event_handlers.iter().any(|handler| handler.event_id() == event::WIDGET_DRAG_START) {
+            widget.trigger_event(event::WIDGET_DRAG_START,
+                                  &Event::Input(Input::Move(Motion::MouseCursor(mouse.x, mouse.y))),
+                                  state);
+        }
+    }
+    // Delivers the incremental move delta since the last drag event to the
+    // captured widget, regardless of whether the cursor is still over it.
+    fn update_drag(&mut self, state: &mut S) {
+        let mouse = self.input_state.mouse;
+        let delta = match self.input_state.drag {
+            Some(ref mut drag) => {
+                let dx = mouse.x - drag.last.x;
+                let dy = mouse.y - drag.last.y;
+                drag.last = mouse;
+                Some((drag.widget, dx, dy))
+            }
+            None => None,
+        };
+        if let Some((node_index, dx, dy)) = delta {
+            let ref mut widget = self.graph[node_index];
+            if widget.event_handlers.iter().any(|handler| handler.event_id() == event::WIDGET_DRAG) {
+                widget.trigger_event(event::WIDGET_DRAG,
+                                      &Event::Input(Input::Move(Motion::MouseRelative(dx, dy))),
+                                      state);
+            }
+        }
+    }
+    // Ends the capture, delivering the total delta from start to end.
+    fn end_drag(&mut self, state: &mut S) {
+        if let Some(drag) = self.input_state.drag.take() {
+            let dx = drag.last.x - drag.start.x;
+            let dy = drag.last.y - drag.start.y;
+            let ref mut widget = self.graph[drag.widget];
+            if widget.event_handlers.iter().any(|handler| handler.event_id() == event::WIDGET_DRAG_END) {
+                widget.trigger_event(event::WIDGET_DRAG_END,
+                                      &Event::Input(Input::Move(Motion::MouseRelative(dx, dy))),
+                                      state);
+            }
+        }
+    }
+    // Moves focus to the next/previous focusable widget, in DFS order over
+    // the graph, wrapping at the ends.
+    pub fn focus_next(&mut self, state: &mut S) {
+        let order = self.focusable_order();
+        if let Some(&next_index) = self.next_focus_candidate(&order, 1) {
+            self.set_focus(state, Some(next_index));
+        }
+    }
+    pub fn focus_previous(&mut self, state: &mut S) {
+        let order = self.focusable_order();
+        if let Some(&prev_index) = self.next_focus_candidate(&order, order.len().wrapping_sub(1)) {
+            self.set_focus(state, Some(prev_index));
+        }
+    }
+    fn next_focus_candidate<'a>(&self, order: &'a [NodeIndex], offset: usize) -> Option<&'a NodeIndex> {
+        if order.is_empty() {
+            return None;
+        }
+        let current_pos = self.input_state.focused.and_then(|current| order.iter().position(|&n| n == current));
+        let next_pos = match current_pos {
+            Some(pos) => (pos + offset) % order.len(),
+            None => 0,
+        };
+        order.get(next_pos)
+    }
+    fn focusable_order(&self) -> Vec<NodeIndex> {
+        let mut dfs = Dfs::new(&self.graph, self.root_index);
+        let mut order = Vec::new();
+        while let Some(node_index) = dfs.next(&self.graph) {
+            if self.graph[node_index].focusable {
+                order.push(node_index);
+            }
+        }
+        order
+    }
+    fn bubble_to_handler(&self, start: NodeIndex, event_id: EventId) -> Option<NodeIndex> {
+        let mut node_index = start;
+        loop {
+            let registered = self.graph[node_index].event_handlers.iter().any(|handler| handler.event_id() == event_id);
+            if registered {
+                return Some(node_index);
+            }
+            match self.graph.neighbors_directed(node_index, Direction::Incoming).next() {
+                Some(parent_index) => node_index = parent_index,
+                None => return None,
+            }
+        }
+    }
+    // Walks the graph in paint order (parents before children, siblings in
+    // insertion order), caching each widget's solved bounds into a per-frame
+    // hitbox list in that same paint order, then resolves the single topmost
+    // widget containing the cursor: the *last* entry that contains it, since
+    // the list is already back-to-front. Depth is not a valid tiebreak here —
+    // a shallow but later-painted sibling (e.g. a popup added after the rest
+    // of the tree) can overlap a deeply-nested descendant of an
+    // earlier-painted sibling while still being drawn on top of it. This
+    // avoids re-solving bounds against a stale solver once dispatch has
+    // picked a winner, and ensures overlapping widgets no longer all react
+    // to one event.
+    fn topmost_under_cursor(&mut self) -> Option<NodeIndex> {
+        let mouse = self.input_state.mouse;
+        let mut hitbox = Vec::new();
+        let mut stack = vec![self.root_index];
+        while let Some(node_index) = stack.pop() {
+            let bounds = self.graph[node_index].layout.bounds(&mut self.solver);
+            hitbox.push((node_index, bounds));
+            // `neighbors` already yields children in reverse-insertion order,
+            // which is exactly what pushing onto this LIFO stack needs to pop
+            // them back off in insertion (paint) order.
+            let children: Vec<NodeIndex> = self.graph.neighbors(node_index).collect();
+            stack.extend(children);
+        }
+        hitbox.into_iter()
+            .filter(|&(node_index, bounds)| (self.graph[node_index].mouse_over_fn)(mouse, bounds))
+            .last()
+            .map(|(node_index, _)| node_index)
+    }
 }
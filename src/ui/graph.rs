@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use petgraph::stable_graph::StableGraph;
 use petgraph::graph::NodeIndex;
-use petgraph::visit::{Dfs, DfsPostOrder};
+use petgraph::visit::Dfs;
 use petgraph::Direction;
 use petgraph::visit::Visitable;
 use petgraph::stable_graph::WalkNeighbors;
@@ -146,25 +146,44 @@ impl NeighborsWalker {
     }
 }
 
+// Returns widgets under the cursor topmost-first, rather than every widget
+// that happens to overlap it. A single hitbox pass walks the graph in paint
+// order (parents before children, siblings in insertion order) recording
+// which widgets contain the point in that same paint (back-to-front) order,
+// so topmost-first is just that list reversed — depth is not a valid
+// tiebreak here, since a shallow but later-painted sibling can overlap a
+// deeply-nested descendant of an earlier-painted sibling while still being
+// drawn on top of it.
 pub struct CursorWidgetWalker {
-    point: Point,
-    dfs: DfsPostOrder<NodeIndex, <Graph as Visitable>::Map>,
+    hits: Vec<WidgetId>,
+    index: usize,
 }
 impl CursorWidgetWalker {
     fn new(point: Point, graph: &Graph, root_index: NodeIndex) -> Self {
-        CursorWidgetWalker {
-            point: point,
-            dfs: DfsPostOrder::new(graph, root_index),
-        }
-    }
-    pub fn next(&mut self, graph: &Graph) -> Option<WidgetId> {
-        while let Some(node_index) = self.dfs.next(graph) {
+        let mut hitbox = Vec::new();
+        let mut stack = vec![root_index];
+        while let Some(node_index) = stack.pop() {
             let ref widget = graph[node_index].widget;
-            if widget.is_mouse_over(self.point) {
-                return Some(widget.id);
+            if widget.is_mouse_over(point) {
+                hitbox.push(widget.id);
             }
+            // `neighbors_directed` already yields children in reverse-insertion
+            // order, which is exactly what pushing onto this LIFO stack needs
+            // to pop them back off in insertion (paint) order.
+            let children: Vec<NodeIndex> =
+                graph.neighbors_directed(node_index, Direction::Outgoing).collect();
+            stack.extend(children);
         }
-        None
+        let hits = hitbox.into_iter().rev().collect();
+        CursorWidgetWalker {
+            hits: hits,
+            index: 0,
+        }
+    }
+    pub fn next(&mut self, _graph: &Graph) -> Option<WidgetId> {
+        let hit = self.hits.get(self.index).cloned();
+        self.index += 1;
+        hit
     }
 }
 pub struct DfsWalker {
@@ -3,6 +3,7 @@ pub mod primitives;
 pub mod text;
 pub mod image;
 pub mod button;
+pub mod style;
 
 use backend::gfx::G2d;
 use graphics::Context;
@@ -18,22 +19,31 @@ use cassowary::Solver;
 
 use std::any::Any;
 
-pub trait EventHandler {
+pub trait EventHandler<S> {
     fn event_id(&self) -> EventId;
-    fn handle_event(&mut self, &Event, &mut Any) -> Option<EventId>;
+    fn handle_event(&mut self, &Event, &mut Any, &mut S) -> Option<EventId>;
 }
 
-pub struct Widget {
-    pub draw_fn: fn(&Any, Rectangle, &mut Resources, Context, &mut G2d),
+pub struct Widget<S> {
+    pub draw_fn: fn(&Any, Rectangle, &mut Resources, Context, &mut G2d, &mut S),
     pub mouse_over_fn: fn(Point, Rectangle) -> bool,
     pub drawable: Box<Any>,
     pub layout: WidgetLayout,
-    pub event_handlers: Vec<Box<EventHandler>>,
+    pub event_handlers: Vec<Box<EventHandler<S>>>,
+    // Whether this widget can receive keyboard focus, e.g. via Tab traversal
+    // or a mouse press. Most widgets are not interactive and leave this false.
+    pub focusable: bool,
+    // Set when the widget's constraints or an edit variable changed, so its
+    // solved bounds need re-fetching and its dirty state propagates to its
+    // descendants (their bounds may depend on it through the solver).
+    pub layout_dirty: bool,
+    // Set when the drawable has mutated and the widget needs to be redrawn.
+    pub paint_dirty: bool,
 }
 
 use input::{Input, Motion};
-impl Widget {
-    pub fn new(draw_fn: fn(&Any, Rectangle, &mut Resources, Context, &mut G2d),
+impl<S> Widget<S> {
+    pub fn new(draw_fn: fn(&Any, Rectangle, &mut Resources, Context, &mut G2d, &mut S),
                drawable: Box<Any>)
                -> Self {
         Widget {
@@ -42,21 +52,42 @@ impl Widget {
             drawable: drawable,
             layout: WidgetLayout::new(),
             event_handlers: Vec::new(),
+            focusable: false,
+            // Widgets start out dirty so the first frame lays out and draws
+            // the whole graph; after that only changed subtrees re-fetch their
+            // bounds from the solver.
+            layout_dirty: true,
+            paint_dirty: true,
         }
     }
+    pub fn set_focusable(&mut self, focusable: bool) -> &mut Self {
+        self.focusable = focusable;
+        self
+    }
+    pub fn mark_dirty(&mut self) {
+        self.layout_dirty = true;
+        self.paint_dirty = true;
+    }
     pub fn print(&self, solver: &mut Solver) {
         println!("{:?}", self.layout.bounds(solver));
     }
-    pub fn draw(&self, resources: &mut Resources, solver: &mut Solver, c: Context, g: &mut G2d) {
-        let bounds = self.layout.bounds(solver);
-        (self.draw_fn)(self.drawable.as_ref(), bounds, resources, c, g);
+    // Takes `bounds` rather than a `Solver` so a caller drawing many widgets
+    // per frame (see `Ui::draw`) can reuse bounds it cached on an earlier
+    // frame instead of re-querying the solver for every widget whether
+    // or not its layout actually changed.
+    pub fn draw(&self, bounds: Rectangle, resources: &mut Resources, c: Context, g: &mut G2d, state: &mut S) {
+        (self.draw_fn)(self.drawable.as_ref(), bounds, resources, c, g, state);
     }
     pub fn is_mouse_over(&self, solver: &mut Solver, mouse: Point) -> bool {
         let bounds = self.layout.bounds(solver);
         (self.mouse_over_fn)(mouse, bounds)
     }
-    pub fn trigger_event(&mut self, id: EventId, event: &Event) -> Option<EventId> {
+    pub fn trigger_event(&mut self, id: EventId, event: &Event, state: &mut S) -> Option<EventId> {
         let event_handler = self.event_handlers.iter_mut().find(|event_handler| event_handler.event_id() == id).unwrap();
-        event_handler.handle_event(event, self.drawable.as_mut())
+        let follow_up = event_handler.handle_event(event, self.drawable.as_mut(), state);
+        // A handler gets mutable access to the drawable, so assume it may
+        // have changed and needs to be repainted.
+        self.paint_dirty = true;
+        follow_up
     }
 }
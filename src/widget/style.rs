@@ -0,0 +1,62 @@
+use super::super::ui::{Theme, ThemeValue};
+
+// Theme-resolution plumbing only: nothing in this crate calls `resolve`.
+// `widgets::text`'s `text_drawable` and `widgets::button`'s
+// `ToggleButtonBuilder` do not call it, so a `Value::Theme(key)` a widget is
+// built with, like the background color in `examples/textbox.rs`, is not
+// looked up against the active `Theme` when that widget draws.
+
+// A style field's value: either a literal the widget was built with, or a
+// named lookup that `resolve` answers from the active `Theme`. A draw path
+// that resolves a `Value::Theme(key)` on every draw picks up a theme swapped
+// in by `Ui::set_theme` without the widget being rebuilt.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<T> {
+    Single(T),
+    Theme(String),
+}
+impl<T> Value<T> {
+    // Resolves this value against `theme`. A `Theme(key)` that names a
+    // missing key, or one whose `ThemeValue` is the wrong kind for `T`,
+    // falls back to `default` rather than failing the draw.
+    pub fn resolve(&self, theme: &Theme, default: T) -> T
+        where T: FromThemeValue + Clone
+    {
+        match *self {
+            Value::Single(ref value) => value.clone(),
+            Value::Theme(ref key) => {
+                theme.get(key).and_then(FromThemeValue::from_theme_value).unwrap_or(default)
+            }
+        }
+    }
+}
+
+// Bridges `Theme`'s untyped `ThemeValue` store to the concrete style types
+// (`[f32; 4]`, `f64`, ...) that `Value::resolve` hands back to widgets.
+pub trait FromThemeValue: Sized {
+    fn from_theme_value(value: &ThemeValue) -> Option<Self>;
+}
+impl FromThemeValue for [f32; 4] {
+    fn from_theme_value(value: &ThemeValue) -> Option<Self> {
+        match *value {
+            ThemeValue::Color(color) => Some(color),
+            _ => None,
+        }
+    }
+}
+impl FromThemeValue for f64 {
+    fn from_theme_value(value: &ThemeValue) -> Option<Self> {
+        match *value {
+            ThemeValue::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+impl FromThemeValue for String {
+    fn from_theme_value(value: &ThemeValue) -> Option<Self> {
+        match *value {
+            ThemeValue::Font(ref font) => Some(font.clone()),
+            _ => None,
+        }
+    }
+}